@@ -1,8 +1,29 @@
 use std::io::{BufReader, Read};
 
+use image::error::{DecodingError, ImageFormatHint};
 use image::{ImageDecoder, ImageError, ImageResult, Progress};
 
-use crate::{QoiHeader, QoiReader};
+use crate::{QoiHeader, QoiReader, END_MARKER};
+
+/// The maximum number of pixels a [`QoiDecoder`] will accept, matching the
+/// reference `qoi.h` implementation's `QOI_PIXELS_MAX`. Guards against
+/// headers that declare a `width * height` large enough to overflow or
+/// exhaust memory downstream.
+const QOI_PIXELS_MAX: u64 = 400_000_000;
+
+/// The number of channels a [`QoiDecoder`] should produce, independent of
+/// how many channels the source file actually stores.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channels {
+    Rgb = 3,
+    Rgba = 4,
+}
+
+impl Channels {
+    fn count(self) -> usize {
+        self as usize
+    }
+}
 
 /// An [`ImageDecoder`] for the [Quite Ok Image Format](https://qoiformat.org).
 ///
@@ -21,6 +42,7 @@ use crate::{QoiHeader, QoiReader};
 pub struct QoiDecoder<R> {
     header: QoiHeader,
     buffer: BufReader<R>,
+    channels: Option<Channels>,
 }
 
 impl<R: Read> QoiDecoder<R> {
@@ -29,7 +51,43 @@ impl<R: Read> QoiDecoder<R> {
         let mut header_bytes = [0; 14];
         buffer.read_exact(&mut header_bytes)?;
         let header = QoiHeader::try_from(&header_bytes[..]).map_err(ImageError::Decoding)?;
-        Ok(Self { header, buffer })
+
+        let pixel_count = header.width as u64 * header.height as u64;
+        if pixel_count > QOI_PIXELS_MAX {
+            return Err(ImageError::Decoding(DecodingError::new(
+                ImageFormatHint::Unknown,
+                "image dimensions exceed the maximum supported pixel count",
+            )));
+        }
+
+        Ok(Self {
+            header,
+            buffer,
+            channels: None,
+        })
+    }
+
+    fn pixel_count(&self) -> u64 {
+        self.header.width as u64 * self.header.height as u64
+    }
+
+    /// Overrides the number of channels the decoder produces, independent of
+    /// the channel count stored in the file's header. Decoding a 3-channel
+    /// file with [`Channels::Rgba`] fills alpha with `0xFF`; decoding a
+    /// 4-channel file with [`Channels::Rgb`] drops the alpha byte.
+    pub fn with_channels(mut self, channels: Channels) -> Self {
+        self.channels = Some(channels);
+        self
+    }
+
+    /// The number of channels the decoder will produce, taking into account
+    /// any override from [`with_channels`](Self::with_channels).
+    pub fn channels(&self) -> Channels {
+        self.channels.unwrap_or(if self.header.is_rgba() {
+            Channels::Rgba
+        } else {
+            Channels::Rgb
+        })
     }
 }
 
@@ -41,48 +99,101 @@ impl<'a, R: Read + 'a> ImageDecoder<'a> for QoiDecoder<R> {
     }
 
     fn color_type(&self) -> image::ColorType {
-        if self.header.is_rgba() {
-            image::ColorType::Rgba8
-        } else {
-            image::ColorType::Rgb8
+        match self.channels() {
+            Channels::Rgba => image::ColorType::Rgba8,
+            Channels::Rgb => image::ColorType::Rgb8,
         }
     }
 
     fn into_reader(self) -> ImageResult<Self::Reader> {
-        Ok(QoiReader::new(self.header, self.buffer))
+        let channels = self.channels().count();
+        Ok(QoiReader::new(self.buffer, channels))
     }
 
     fn scanline_bytes(&self) -> u64 {
         self.color_type().bytes_per_pixel() as u64
     }
 
+    // `Progress` has no public constructor in the pinned `image` 0.23 line
+    // (its fields are private to that crate), so there is no way for an
+    // external decoder to report anything other than the default
+    // byte-chunked progress `ImageDecoder` already gives callers for free.
+    // This override validates the stream as it's read and fills runs in
+    // bulk; it intentionally never calls `progress_callback` itself.
     fn read_image_with_progress<F: Fn(Progress)>(
         self,
         mut buf: &mut [u8],
         _progress_callback: F,
     ) -> ImageResult<()> {
-        let total_bytes = self.total_bytes() as usize;
-        assert_eq!(buf.len(), total_bytes);
+        let total_bytes = self.total_bytes();
+        assert_eq!(buf.len() as u64, total_bytes);
 
+        let pixel_count = self.pixel_count();
         let mut reader = self.into_reader()?;
 
-        while !buf.is_empty() {
+        let mut pixels_read = 0u64;
+
+        while pixels_read < pixel_count {
             let pixel = reader.load_next_pixel()?;
-            for _ in 0..(pixel.count / pixel.chans) {
-                buf[..pixel.chans].copy_from_slice(&pixel.bytes[..pixel.chans]);
-                buf = &mut buf[pixel.chans..]
+
+            // a run can claim more pixels than the header declared remain;
+            // clamp it to what's left in `buf` before indexing into it, and
+            // treat the overrun itself as a corrupt stream below.
+            let remaining = pixel_count - pixels_read;
+            let chunk_pixels = (pixel.count / pixel.chans) as u64;
+            let overrun = chunk_pixels > remaining;
+            let chunk_bytes = if overrun {
+                remaining as usize * pixel.chans
+            } else {
+                pixel.count
+            };
+
+            if chunk_bytes > pixel.chans {
+                fill_run(&mut buf[..chunk_bytes], &pixel.bytes[..pixel.chans]);
+            } else {
+                buf[..chunk_bytes].copy_from_slice(&pixel.bytes[..chunk_bytes]);
+            }
+            buf = &mut buf[chunk_bytes..];
+
+            pixels_read += (chunk_bytes / pixel.chans) as u64;
+
+            if overrun {
+                return Err(ImageError::Decoding(DecodingError::new(
+                    ImageFormatHint::Unknown,
+                    "pixel run overruns the declared image dimensions",
+                )));
             }
         }
 
+        if reader.read_end_marker()? != END_MARKER {
+            return Err(ImageError::Decoding(DecodingError::new(
+                ImageFormatHint::Unknown,
+                "missing or invalid QOI end marker",
+            )));
+        }
+
         Ok(())
     }
 }
 
+/// Fills `buf` with repeats of `seed` by doubling the already-filled region
+/// each pass, rather than copying `seed` in one memcpy per pixel.
+fn fill_run(buf: &mut [u8], seed: &[u8]) {
+    buf[..seed.len()].copy_from_slice(seed);
+
+    let mut filled = seed.len();
+    while filled < buf.len() {
+        let copy_len = filled.min(buf.len() - filled);
+        buf.copy_within(0..copy_len, filled);
+        filled += copy_len;
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use std::{fs::File, path::PathBuf};
+    use std::{cell::Cell, fs::File, io::Cursor, path::PathBuf};
 
-    use image::{codecs::png::PngDecoder, DynamicImage};
+    use image::{codecs::png::PngDecoder, DynamicImage, ImageDecoder};
     use test_case::test_case;
 
     use crate::QoiDecoder;
@@ -109,4 +220,72 @@ mod tests {
 
         assert_eq!(qoi, png);
     }
+
+    /// Builds a bare 14-byte QOI header, with no body, so malformed-stream
+    /// tests can append whatever bytes they need to exercise.
+    fn header_bytes(width: u32, height: u32, channels: u8) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(14);
+        bytes.extend_from_slice(b"qoif");
+        bytes.extend_from_slice(&width.to_be_bytes());
+        bytes.extend_from_slice(&height.to_be_bytes());
+        bytes.push(channels);
+        bytes.push(0); // colorspace
+        bytes
+    }
+
+    #[test]
+    fn rejects_dimensions_over_the_pixel_limit() {
+        // 30_000 * 30_000 = 900_000_000 > QOI_PIXELS_MAX
+        let data = header_bytes(30_000, 30_000, 4);
+        assert!(QoiDecoder::new(Cursor::new(data)).is_err());
+    }
+
+    #[test]
+    fn rejects_a_truncated_stream() {
+        let mut data = header_bytes(1, 1, 4);
+        data.extend_from_slice(&[0xff, 1, 2, 3, 255]); // one RGBA pixel, no end marker
+        let decoder = QoiDecoder::new(Cursor::new(data)).unwrap();
+        assert!(DynamicImage::from_decoder(decoder).is_err());
+    }
+
+    #[test]
+    fn rejects_a_corrupted_end_marker() {
+        let mut data = header_bytes(1, 1, 4);
+        data.extend_from_slice(&[0xff, 1, 2, 3, 255]);
+        data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0]); // should end in 1, not 0
+        let decoder = QoiDecoder::new(Cursor::new(data)).unwrap();
+        assert!(DynamicImage::from_decoder(decoder).is_err());
+    }
+
+    #[test]
+    fn rejects_a_run_that_overruns_the_image() {
+        let mut data = header_bytes(1, 1, 4);
+        data.push(0b1100_0001); // QOI_OP_RUN, run = 2, but only 1 pixel declared
+        data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+        let decoder = QoiDecoder::new(Cursor::new(data)).unwrap();
+        assert!(DynamicImage::from_decoder(decoder).is_err());
+    }
+
+    #[test]
+    fn decodes_successfully_without_ever_calling_the_progress_callback() {
+        // `Progress` has no public constructor in the pinned `image` version,
+        // so `read_image_with_progress` can't build one itself; confirm it
+        // still decodes correctly and simply never invokes the callback.
+        let mut data = header_bytes(2, 2, 4);
+        for pixel in [[1, 1, 1, 255], [2, 2, 2, 255], [3, 3, 3, 255], [4, 4, 4, 255]] {
+            data.push(0xff);
+            data.extend_from_slice(&pixel);
+        }
+        data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+
+        let decoder = QoiDecoder::new(Cursor::new(data)).unwrap();
+        let mut buf = vec![0; decoder.total_bytes() as usize];
+        let calls = Cell::new(0);
+        decoder
+            .read_image_with_progress(&mut buf, |_| calls.set(calls.get() + 1))
+            .unwrap();
+
+        assert_eq!(calls.get(), 0);
+        assert_eq!(buf, [1, 1, 1, 255, 2, 2, 2, 255, 3, 3, 3, 255, 4, 4, 4, 255]);
+    }
 }
@@ -0,0 +1,203 @@
+use std::io::Write;
+use std::num::Wrapping;
+
+use image::error::{ImageFormatHint, UnsupportedError, UnsupportedErrorKind};
+use image::{ColorType, ImageEncoder, ImageError, ImageResult};
+
+use crate::{QoiHeader, Rgba, END_MARKER};
+
+const QOI_OP_INDEX: u8 = 0b0000_0000;
+const QOI_OP_DIFF: u8 = 0b0100_0000;
+const QOI_OP_LUMA: u8 = 0b1000_0000;
+const QOI_OP_RUN: u8 = 0b1100_0000;
+const QOI_OP_RGB: u8 = 0xfe;
+const QOI_OP_RGBA: u8 = 0xff;
+
+/// An [`ImageEncoder`] for the [Quite Ok Image Format](https://qoiformat.org).
+///
+/// ```
+/// use image::ImageEncoder;
+/// use image_qoi::QoiEncoder;
+///
+/// # fn main() -> image::ImageResult<()> {
+/// let pixels = [0u8, 0, 0, 255, 255, 255, 255, 255];
+/// let mut out = Vec::new();
+/// QoiEncoder::new(&mut out).write_image(&pixels, 2, 1, image::ColorType::Rgba8)?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct QoiEncoder<W> {
+    writer: W,
+}
+
+impl<W: Write> QoiEncoder<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+impl<W: Write> ImageEncoder for QoiEncoder<W> {
+    fn write_image(
+        mut self,
+        buf: &[u8],
+        width: u32,
+        height: u32,
+        color_type: ColorType,
+    ) -> ImageResult<()> {
+        let channels: usize = match color_type {
+            ColorType::Rgb8 => 3,
+            ColorType::Rgba8 => 4,
+            _ => {
+                return Err(ImageError::Unsupported(
+                    UnsupportedError::from_format_and_kind(
+                        ImageFormatHint::Unknown,
+                        UnsupportedErrorKind::Color(color_type.into()),
+                    ),
+                ))
+            }
+        };
+
+        let header = QoiHeader::new(width, height, channels as u8);
+        self.writer.write_all(&header.to_bytes())?;
+
+        let mut pixels = [Rgba::ZERO; 64];
+        let mut latest = Rgba::INIT;
+        let mut run = 0u8;
+
+        for chunk in buf.chunks_exact(channels) {
+            let pixel = if channels == 4 {
+                Rgba::from_bytes([chunk[0], chunk[1], chunk[2], chunk[3]])
+            } else {
+                Rgba::from_bytes([chunk[0], chunk[1], chunk[2], latest.alpha().0])
+            };
+
+            if pixel == latest {
+                run += 1;
+                if run == 62 {
+                    self.writer.write_all(&[QOI_OP_RUN | (run - 1)])?;
+                    run = 0;
+                }
+                continue;
+            }
+
+            if run > 0 {
+                self.writer.write_all(&[QOI_OP_RUN | (run - 1)])?;
+                run = 0;
+            }
+
+            let index = pixel.hash() as usize;
+            if pixels[index] == pixel {
+                self.writer.write_all(&[QOI_OP_INDEX | index as u8])?;
+            } else if pixel.alpha() == latest.alpha() {
+                if let Some(byte) = diff(latest, pixel) {
+                    self.writer.write_all(&[byte])?;
+                } else if let Some(bytes) = luma(latest, pixel) {
+                    self.writer.write_all(&bytes)?;
+                } else {
+                    let [r, g, b, _] = pixel.bytes();
+                    self.writer.write_all(&[QOI_OP_RGB, r, g, b])?;
+                }
+            } else {
+                let [r, g, b, a] = pixel.bytes();
+                self.writer.write_all(&[QOI_OP_RGBA, r, g, b, a])?;
+            }
+
+            pixels[index] = pixel;
+            latest = pixel;
+        }
+
+        if run > 0 {
+            self.writer.write_all(&[QOI_OP_RUN | (run - 1)])?;
+        }
+
+        self.writer.write_all(&END_MARKER)?;
+        Ok(())
+    }
+}
+
+/// The wrapping signed delta between two channel values, as used by
+/// `QOI_OP_DIFF`/`QOI_OP_LUMA`.
+fn channel_diff(a: Wrapping<u8>, b: Wrapping<u8>) -> i8 {
+    (a - b).0 as i8
+}
+
+fn diff(latest: Rgba, pixel: Rgba) -> Option<u8> {
+    let Rgba([lr, lg, lb, _]) = latest;
+    let Rgba([pr, pg, pb, _]) = pixel;
+
+    let dr = channel_diff(pr, lr);
+    let dg = channel_diff(pg, lg);
+    let db = channel_diff(pb, lb);
+
+    if (-2..=1).contains(&dr) && (-2..=1).contains(&dg) && (-2..=1).contains(&db) {
+        Some(QOI_OP_DIFF | ((dr + 2) as u8) << 4 | ((dg + 2) as u8) << 2 | (db + 2) as u8)
+    } else {
+        None
+    }
+}
+
+fn luma(latest: Rgba, pixel: Rgba) -> Option<[u8; 2]> {
+    let Rgba([lr, lg, lb, _]) = latest;
+    let Rgba([pr, pg, pb, _]) = pixel;
+
+    let dg = channel_diff(pg, lg);
+    let dr_dg = channel_diff(pr, lr).wrapping_sub(dg);
+    let db_dg = channel_diff(pb, lb).wrapping_sub(dg);
+
+    if (-32..=31).contains(&dg) && (-8..=7).contains(&dr_dg) && (-8..=7).contains(&db_dg) {
+        Some([
+            QOI_OP_LUMA | (dg + 32) as u8,
+            ((dr_dg + 8) as u8) << 4 | (db_dg + 8) as u8,
+        ])
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::Cursor, path::PathBuf};
+
+    use image::{DynamicImage, ImageDecoder, ImageEncoder};
+    use test_case::test_case;
+
+    use crate::{QoiDecoder, QoiEncoder};
+
+    /// Encodes a decoded fixture back to QOI and checks it matches the
+    /// checked-in bitstream byte-for-byte, then decodes the re-encoded
+    /// bytes and checks they round-trip to the same pixels.
+    #[test_case("dice")]
+    #[test_case("kodim10")]
+    #[test_case("kodim23")]
+    #[test_case("qoi_logo")]
+    #[test_case("testcard_rgba")]
+    #[test_case("testcard")]
+    #[test_case("wikipedia_008")]
+    fn round_trips_and_matches_the_fixture(file: &str) {
+        let qoi_path = PathBuf::from("qoi_test_images").join(file).with_extension("qoi");
+        let original = std::fs::read(&qoi_path).unwrap();
+
+        let decoder = QoiDecoder::new(File::open(&qoi_path).unwrap()).unwrap();
+        let (width, height) = decoder.dimensions();
+        let color_type = decoder.color_type();
+        let buf = into_raw(DynamicImage::from_decoder(decoder).unwrap());
+
+        let mut encoded = Vec::new();
+        QoiEncoder::new(&mut encoded)
+            .write_image(&buf, width, height, color_type)
+            .unwrap();
+        assert_eq!(encoded, original);
+
+        let decoder = QoiDecoder::new(Cursor::new(encoded)).unwrap();
+        let round_tripped = into_raw(DynamicImage::from_decoder(decoder).unwrap());
+        assert_eq!(round_tripped, buf);
+    }
+
+    fn into_raw(image: DynamicImage) -> Vec<u8> {
+        match image {
+            DynamicImage::ImageRgb8(buf) => buf.into_raw(),
+            DynamicImage::ImageRgba8(buf) => buf.into_raw(),
+            other => panic!("unexpected color type in fixture: {other:?}"),
+        }
+    }
+}
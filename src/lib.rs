@@ -1,10 +1,54 @@
-use std::{num::Wrapping, mem::MaybeUninit};
+#![cfg_attr(not(feature = "std"), no_std)]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+use core::{mem::MaybeUninit, num::Wrapping};
+
+#[cfg(feature = "std")]
 use image::error::{DecodingError, ImageFormatHint};
 
+#[cfg(feature = "std")]
 mod decoder;
+#[cfg(feature = "std")]
+mod encoder;
+#[cfg(feature = "std")]
 mod reader;
-pub use {decoder::QoiDecoder, reader::QoiReader};
+mod slice;
+
+#[cfg(feature = "std")]
+pub use {
+    decoder::{Channels, QoiDecoder},
+    encoder::QoiEncoder,
+    reader::QoiReader,
+};
+#[cfg(feature = "alloc")]
+pub use slice::decode_to_vec;
+pub use slice::decode_to_buf;
+
+/// Error produced by the allocation-free slice decoder in [`decode_to_buf`]
+/// (and [`decode_to_vec`] behind the `alloc` feature).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// the data is too short to contain a QOI header, or ran out before the
+    /// declared pixel count was satisfied
+    UnexpectedEof,
+    /// the data does not start with the `qoif` magic bytes
+    InvalidMagic,
+    /// the header's `width * height * channels` overflows `usize`
+    DimensionsOverflow,
+    /// the output buffer is too small to hold the decoded image
+    OutputTooSmall,
+    /// a `QOI_OP_RUN` claimed more pixels than the header declared
+    RunOverrun,
+    /// the 8-byte end marker is missing, truncated, or doesn't match the
+    /// mandatory seven `0x00` bytes followed by `0x01`
+    MissingEndMarker,
+}
+
+/// The 8-byte sequence that must terminate every QOI bitstream, shared by
+/// the `std` reader/decoder and the allocation-free slice decoder.
+pub(crate) const END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
 
 #[repr(C)]
 #[derive(PartialEq, Eq, Clone, Copy)]
@@ -41,20 +85,37 @@ struct QoiHeader {
 }
 
 impl QoiHeader {
+    fn new(width: u32, height: u32, channels: u8) -> Self {
+        Self {
+            magic: *b"qoif",
+            width: width.to_be(),
+            height: height.to_be(),
+            channels,
+            colorspace: 0,
+        }
+    }
+
     fn is_rgba(&self) -> bool {
         self.channels == 4
     }
-}
 
-impl TryFrom<&[u8]> for QoiHeader {
-    type Error = image::error::DecodingError;
+    fn to_bytes(&self) -> [u8; 14] {
+        unsafe {
+            let mut bytes = MaybeUninit::<[u8; 14]>::uninit();
+            bytes
+                .as_mut_ptr()
+                .cast::<u8>()
+                .copy_from_nonoverlapping((self as *const Self).cast::<u8>(), 14);
+            bytes.assume_init()
+        }
+    }
 
-    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+    /// Parses the 14-byte QOI header from a byte slice without touching
+    /// `std::io`, so it can be shared between the `std` reader/decoder and
+    /// the allocation-free [`slice`](crate::slice) decode path.
+    fn parse(value: &[u8]) -> Result<Self, Error> {
         if value.len() < 14 {
-            return Err(DecodingError::new(
-                ImageFormatHint::Unknown,
-                "not enough bytes for header",
-            ));
+            return Err(Error::UnexpectedEof);
         }
 
         let mut this = unsafe {
@@ -66,10 +127,7 @@ impl TryFrom<&[u8]> for QoiHeader {
         };
 
         if &this.magic != b"qoif" {
-            return Err(DecodingError::new(
-                ImageFormatHint::Unknown,
-                "qoif magic header not found",
-            ));
+            return Err(Error::InvalidMagic);
         }
 
         this.width = u32::from_be(this.width);
@@ -78,3 +136,24 @@ impl TryFrom<&[u8]> for QoiHeader {
         Ok(this)
     }
 }
+
+#[cfg(feature = "std")]
+impl TryFrom<&[u8]> for QoiHeader {
+    type Error = DecodingError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        Self::parse(value).map_err(|err| {
+            let message = match err {
+                Error::UnexpectedEof => "not enough bytes for header",
+                Error::InvalidMagic => "qoif magic header not found",
+                Error::DimensionsOverflow
+                | Error::OutputTooSmall
+                | Error::RunOverrun
+                | Error::MissingEndMarker => {
+                    unreachable!("header parsing never computes pixel totals or reads pixels")
+                }
+            };
+            DecodingError::new(ImageFormatHint::Unknown, message)
+        })
+    }
+}
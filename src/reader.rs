@@ -3,28 +3,30 @@ use std::{
     num::Wrapping,
 };
 
-use crate::{QoiHeader, Rgba};
+use crate::Rgba;
 
 pub struct QoiReader<R> {
-    header: QoiHeader,
     buffer: BufReader<R>,
     pixels: [Rgba; 64],
     latest: Rgba,
+    /// the number of channels to emit per pixel; may differ from
+    /// `header.channels` when the caller requested a conversion via
+    /// [`QoiDecoder::with_channels`](crate::QoiDecoder::with_channels)
+    out_channels: usize,
     remain: QoiRemaining,
 }
 
 impl<R> QoiReader<R> {
-    pub(crate) fn new(header: QoiHeader, buffer: BufReader<R>) -> Self {
-        let chans = header.channels as usize;
+    pub(crate) fn new(buffer: BufReader<R>, out_channels: usize) -> Self {
         Self {
-            header,
             buffer,
             pixels: [Rgba::ZERO; 64],
             latest: Rgba::INIT,
+            out_channels,
             remain: QoiRemaining {
                 bytes: [0; 4],
                 count: 0,
-                chans,
+                chans: out_channels,
             },
         }
     }
@@ -86,8 +88,8 @@ impl<R: Read> QoiReader<R> {
                     let run = (tag & 0b0011_1111) + 1;
                     Ok(QoiRemaining {
                         bytes: self.latest.bytes(),
-                        chans: self.header.channels as usize,
-                        count: run as usize * self.header.channels as usize,
+                        chans: self.out_channels,
+                        count: run as usize * self.out_channels,
                     })
                 }
                 0b10 => {
@@ -123,8 +125,8 @@ impl<R: Read> QoiReader<R> {
         self.pixels[pixel.hash() as usize] = pixel;
         QoiRemaining {
             bytes: pixel.bytes(),
-            chans: self.header.channels as usize,
-            count: self.header.channels as usize,
+            chans: self.out_channels,
+            count: self.out_channels,
         }
     }
     fn read_tag(&mut self) -> std::io::Result<u8> {
@@ -142,4 +144,12 @@ impl<R: Read> QoiReader<R> {
         self.buffer.read_exact(&mut rgba[0..3])?;
         Ok(self.save_pixel(Rgba::from_bytes(rgba)))
     }
+
+    /// Reads the trailing 8-byte end-of-stream marker, once every pixel has
+    /// been consumed.
+    pub(crate) fn read_end_marker(&mut self) -> std::io::Result<[u8; 8]> {
+        let mut marker = [0; 8];
+        self.buffer.read_exact(&mut marker)?;
+        Ok(marker)
+    }
 }
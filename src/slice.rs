@@ -0,0 +1,182 @@
+//! A pure `&[u8]` decode path that never touches `std::io`, so it works
+//! under `no_std` + `alloc` (and in `no_std` without `alloc`, given a
+//! caller-provided output buffer).
+
+use core::num::Wrapping;
+
+use crate::{Error, QoiHeader, Rgba, END_MARKER};
+
+/// Decodes a QOI image from `data` straight into `out`, without allocating.
+///
+/// `out` must be at least `width * height * channels` bytes, where
+/// `channels` is whatever the file's header declares (3 for RGB, 4 for
+/// RGBA); returns the number of bytes written.
+pub fn decode_to_buf(data: &[u8], out: &mut [u8]) -> Result<usize, Error> {
+    let header = QoiHeader::parse(data)?;
+    let mut data = &data[14..];
+
+    let channels = header.channels as usize;
+    let total = (header.width as usize)
+        .checked_mul(header.height as usize)
+        .and_then(|pixels| pixels.checked_mul(channels))
+        .ok_or(Error::DimensionsOverflow)?;
+
+    if out.len() < total {
+        return Err(Error::OutputTooSmall);
+    }
+
+    let mut pixels = [Rgba::ZERO; 64];
+    let mut latest = Rgba::INIT;
+    let mut written = 0;
+
+    while written < total {
+        let pixel = match data {
+            // a truncated RGBA/RGB tag must not fall through to the run arm
+            // below and get misread as a `QOI_OP_RUN`
+            [0xff, ..] if data.len() < 5 => return Err(Error::UnexpectedEof),
+            [0xfe, ..] if data.len() < 4 => return Err(Error::UnexpectedEof),
+            [0xff, r, g, b, a, tail @ ..] => {
+                data = tail;
+                Rgba::from_bytes([*r, *g, *b, *a])
+            }
+            [0xfe, r, g, b, tail @ ..] => {
+                data = tail;
+                Rgba::from_bytes([*r, *g, *b, latest.alpha().0])
+            }
+            [tag, tail @ ..] if tag >> 6 == 0b11 => {
+                data = tail;
+                let run = (tag & 0b0011_1111) as usize + 1;
+                let remaining = (total - written) / channels;
+                if run > remaining {
+                    return Err(Error::RunOverrun);
+                }
+                let bytes = latest.bytes();
+                for _ in 0..run {
+                    out[written..written + channels].copy_from_slice(&bytes[..channels]);
+                    written += channels;
+                }
+                continue;
+            }
+            [tag, dr_db, tail @ ..] if tag >> 6 == 0b10 => {
+                data = tail;
+                let dg = Wrapping(tag & 0b0011_1111) - Wrapping(32);
+                let dr_dg = Wrapping(dr_db >> 4) - Wrapping(8);
+                let db_dg = Wrapping(dr_db & 0b0000_1111) - Wrapping(8);
+                let dr = dr_dg + dg;
+                let db = db_dg + dg;
+                let Rgba([r, g, b, a]) = latest;
+                Rgba([r + dr, g + dg, b + db, a])
+            }
+            [tag, tail @ ..] if tag >> 6 == 0b01 => {
+                data = tail;
+                let dr = Wrapping((tag >> 4) & 0b0011) - Wrapping(2);
+                let dg = Wrapping((tag >> 2) & 0b0011) - Wrapping(2);
+                let db = Wrapping(tag & 0b0011) - Wrapping(2);
+                let Rgba([r, g, b, a]) = latest;
+                Rgba([r + dr, g + dg, b + db, a])
+            }
+            // a lone LUMA tag with its second byte truncated would otherwise
+            // fall through to the index arm below and silently mis-decode
+            [tag] if tag >> 6 == 0b10 => return Err(Error::UnexpectedEof),
+            [tag, tail @ ..] => {
+                data = tail;
+                pixels[(tag & 0b0011_1111) as usize]
+            }
+            [] => return Err(Error::UnexpectedEof),
+        };
+
+        out[written..written + channels].copy_from_slice(&pixel.bytes()[..channels]);
+        written += channels;
+        pixels[pixel.hash() as usize] = pixel;
+        latest = pixel;
+    }
+
+    if !data.starts_with(&END_MARKER) {
+        return Err(Error::MissingEndMarker);
+    }
+
+    Ok(written)
+}
+
+/// Like [`decode_to_buf`], but allocates the output buffer itself.
+#[cfg(feature = "alloc")]
+pub fn decode_to_vec(data: &[u8]) -> Result<alloc::vec::Vec<u8>, Error> {
+    let header = QoiHeader::parse(data)?;
+    let channels = header.channels as usize;
+    let total = (header.width as usize)
+        .checked_mul(header.height as usize)
+        .and_then(|pixels| pixels.checked_mul(channels))
+        .ok_or(Error::DimensionsOverflow)?;
+
+    let mut out = alloc::vec![0u8; total];
+    decode_to_buf(data, &mut out)?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "std")]
+    use std::vec::Vec;
+
+    #[cfg(all(feature = "alloc", not(feature = "std")))]
+    use alloc::vec::Vec;
+
+    use super::decode_to_buf;
+    use crate::Error;
+
+    /// Builds a bare 14-byte QOI header, with no body, so malformed-stream
+    /// tests can append whatever bytes they need to exercise.
+    fn header_bytes(width: u32, height: u32, channels: u8) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(14);
+        bytes.extend_from_slice(b"qoif");
+        bytes.extend_from_slice(&width.to_be_bytes());
+        bytes.extend_from_slice(&height.to_be_bytes());
+        bytes.push(channels);
+        bytes.push(0); // colorspace
+        bytes
+    }
+
+    #[test]
+    fn decodes_a_single_rgba_pixel() {
+        let mut data = header_bytes(1, 1, 4);
+        data.extend_from_slice(&[0xff, 1, 2, 3, 255]);
+        data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+
+        let mut out = [0u8; 4];
+        let written = decode_to_buf(&data, &mut out).unwrap();
+        assert_eq!(written, 4);
+        assert_eq!(out, [1, 2, 3, 255]);
+    }
+
+    #[test]
+    fn rejects_a_run_that_overruns_the_image() {
+        let mut data = header_bytes(1, 1, 4);
+        data.push(0b1100_0001); // QOI_OP_RUN, run = 2, but only 1 pixel declared
+        data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+
+        let mut out = [0u8; 4];
+        assert_eq!(decode_to_buf(&data, &mut out), Err(Error::RunOverrun));
+    }
+
+    #[test]
+    fn rejects_a_truncated_rgb_tag() {
+        let mut data = header_bytes(1, 1, 3);
+        data.extend_from_slice(&[0xfe, 1, 2]); // QOI_OP_RGB with only 2 of 3 bytes
+
+        let mut out = [0u8; 3];
+        assert_eq!(decode_to_buf(&data, &mut out), Err(Error::UnexpectedEof));
+    }
+
+    #[test]
+    fn rejects_a_missing_end_marker() {
+        let mut data = header_bytes(1, 1, 4);
+        data.extend_from_slice(&[0xff, 1, 2, 3, 255]);
+        data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0]); // should end in 1, not 0
+
+        let mut out = [0u8; 4];
+        assert_eq!(
+            decode_to_buf(&data, &mut out),
+            Err(Error::MissingEndMarker)
+        );
+    }
+}